@@ -0,0 +1,97 @@
+use std::{collections::HashMap, path::PathBuf};
+
+use anyhow::{Context, Result};
+use directories::ProjectDirs;
+use embedded_graphics_simulator::BinaryColorTheme;
+use serde::Deserialize;
+
+/// One device profile, as defined under `[profiles.<name>]` in the config file.
+#[derive(Deserialize, Debug, Clone)]
+pub struct ProfileConfig {
+    pub api_key: String,
+    #[serde(default = "default_base_url")]
+    pub base_url: String,
+    #[serde(default = "default_width")]
+    pub width: u32,
+    #[serde(default = "default_height")]
+    pub height: u32,
+    #[serde(default)]
+    pub theme: DisplayTheme,
+    pub notify_sound: Option<PathBuf>,
+    #[serde(default)]
+    pub notify_desktop: bool,
+}
+
+fn default_base_url() -> String {
+    "https://usetrmnl.com".to_string()
+}
+
+fn default_width() -> u32 {
+    800
+}
+
+fn default_height() -> u32 {
+    480
+}
+
+/// Mirrors `BinaryColorTheme`, which doesn't implement `Deserialize`.
+#[derive(Deserialize, Debug, Clone, Copy, Default)]
+#[serde(rename_all = "snake_case")]
+pub enum DisplayTheme {
+    #[default]
+    Default,
+    OledBlue,
+    OledWhite,
+    LcdWhite,
+}
+
+impl DisplayTheme {
+    pub fn to_simulator_theme(self) -> BinaryColorTheme {
+        match self {
+            DisplayTheme::Default => BinaryColorTheme::Default,
+            DisplayTheme::OledBlue => BinaryColorTheme::OledBlue,
+            DisplayTheme::OledWhite => BinaryColorTheme::OledWhite,
+            DisplayTheme::LcdWhite => BinaryColorTheme::LcdWhite,
+        }
+    }
+}
+
+/// Top-level shape of the TOML config file.
+#[derive(Deserialize, Debug, Clone, Default)]
+pub struct Config {
+    pub default_profile: Option<String>,
+    #[serde(default)]
+    pub profiles: HashMap<String, ProfileConfig>,
+}
+
+impl Config {
+    /// Loads the config file from the platform config dir, if one exists.
+    pub fn load() -> Result<Option<Self>> {
+        let Some(path) = Self::default_path() else {
+            return Ok(None);
+        };
+        if !path.exists() {
+            return Ok(None);
+        }
+
+        let raw = std::fs::read_to_string(&path)
+            .with_context(|| format!("Failed to read config file at {}", path.display()))?;
+        let config: Config = toml::from_str(&raw)
+            .with_context(|| format!("Failed to parse config file at {}", path.display()))?;
+        Ok(Some(config))
+    }
+
+    pub fn default_path() -> Option<PathBuf> {
+        ProjectDirs::from("", "", "byodt").map(|dirs| dirs.config_dir().join("config.toml"))
+    }
+
+    /// Falls back to `default_profile` when `name` is `None`.
+    pub fn profile(&self, name: Option<&str>) -> Result<&ProfileConfig> {
+        let name = name
+            .or(self.default_profile.as_deref())
+            .context("No --profile given and no default_profile set in the config file")?;
+        self.profiles
+            .get(name)
+            .with_context(|| format!("No profile named '{name}' in the config file"))
+    }
+}
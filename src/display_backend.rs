@@ -0,0 +1,223 @@
+use anyhow::{Result, anyhow};
+use embedded_graphics::{image::Image, pixelcolor::BinaryColor, prelude::*};
+use embedded_graphics_simulator::{
+    BinaryColorTheme, OutputSettingsBuilder, SimulatorDisplay, SimulatorEvent, Window,
+    sdl2::Keycode,
+};
+use log::debug;
+use ratatui::{
+    Terminal,
+    backend::CrosstermBackend,
+    crossterm::{
+        event::{self, Event, KeyCode},
+        execute,
+        terminal::{EnterAlternateScreen, LeaveAlternateScreen, disable_raw_mode, enable_raw_mode},
+    },
+    style::{Color, Style},
+    text::{Line, Span},
+    widgets::Paragraph,
+};
+use std::{io, time::Duration};
+use tinybmp::Bmp;
+
+/// A still-encoded BMP frame, ready to hand to whichever backend is rendering.
+pub struct BmpFrame<'a>(pub &'a [u8]);
+
+/// Something the user did that `run_display` needs to react to.
+pub enum UiEvent {
+    Quit,
+    ManualRefresh,
+    ButtonPress(ButtonPress),
+}
+
+/// The three press kinds a real TRMNL device button can send.
+#[derive(Debug, Clone, Copy)]
+pub enum ButtonPress {
+    Short,
+    Long,
+    Double,
+}
+
+impl ButtonPress {
+    pub fn as_header_value(self) -> &'static str {
+        match self {
+            ButtonPress::Short => "short",
+            ButtonPress::Long => "long",
+            ButtonPress::Double => "double",
+        }
+    }
+}
+
+/// A render target for the device's `BinaryColor` frame.
+pub trait DisplayBackend {
+    fn draw(&mut self, frame: &BmpFrame) -> Result<()>;
+    fn set_status_line(&mut self, text: &str);
+    fn poll_events(&mut self) -> Vec<UiEvent>;
+}
+
+/// The original SDL window backend.
+pub struct SimulatorBackend {
+    window: Window,
+    display: SimulatorDisplay<BinaryColor>,
+}
+
+impl SimulatorBackend {
+    pub fn new(theme: BinaryColorTheme, size: Size) -> Self {
+        let output_settings = OutputSettingsBuilder::new()
+            .scale(1)
+            .pixel_spacing(1)
+            .theme(theme)
+            .build();
+        Self {
+            window: Window::new("TRMNL", &output_settings),
+            display: SimulatorDisplay::<BinaryColor>::new(size),
+        }
+    }
+}
+
+impl DisplayBackend for SimulatorBackend {
+    fn draw(&mut self, frame: &BmpFrame) -> Result<()> {
+        let bmp = Bmp::<BinaryColor>::from_slice(frame.0).map_err(|e| anyhow!("{:?}", e))?;
+        Image::new(&bmp, Point::zero())
+            .draw(&mut self.display)
+            .map_err(|e| anyhow!("{:?}", e))
+    }
+
+    fn set_status_line(&mut self, _text: &str) {
+        // The SDL window has no room for a status line.
+    }
+
+    fn poll_events(&mut self) -> Vec<UiEvent> {
+        // Has to run every tick or the window crashes, regardless of whether
+        // a new frame was drawn.
+        self.window.update(&self.display);
+
+        self.window
+            .events()
+            .filter_map(|event| match event {
+                SimulatorEvent::Quit => Some(UiEvent::Quit),
+                SimulatorEvent::KeyDown {
+                    keycode: Keycode::Return,
+                    ..
+                } => {
+                    debug!("Return key pressed");
+                    Some(UiEvent::ManualRefresh)
+                }
+                // Simulated device buttons: distinct keys stand in for the
+                // short/long/double press gestures a real TRMNL sends.
+                SimulatorEvent::KeyDown {
+                    keycode: Keycode::S, ..
+                } => Some(UiEvent::ButtonPress(ButtonPress::Short)),
+                SimulatorEvent::KeyDown {
+                    keycode: Keycode::L, ..
+                } => Some(UiEvent::ButtonPress(ButtonPress::Long)),
+                SimulatorEvent::KeyDown {
+                    keycode: Keycode::D, ..
+                } => Some(UiEvent::ButtonPress(ButtonPress::Double)),
+                SimulatorEvent::KeyDown { keycode, .. } => {
+                    debug!("Unhandled keycode: {:?}", keycode);
+                    None
+                }
+                _ => None,
+            })
+            .collect()
+    }
+}
+
+/// Renders into the terminal with half-block characters, for SSH sessions
+/// with no display.
+pub struct TuiBackend {
+    terminal: Terminal<CrosstermBackend<io::Stdout>>,
+    status_line: String,
+}
+
+impl TuiBackend {
+    pub fn new() -> Result<Self> {
+        enable_raw_mode()?;
+        let mut stdout = io::stdout();
+        execute!(stdout, EnterAlternateScreen)?;
+        let terminal = Terminal::new(CrosstermBackend::new(stdout))?;
+        Ok(Self {
+            terminal,
+            status_line: String::new(),
+        })
+    }
+}
+
+impl Drop for TuiBackend {
+    fn drop(&mut self) {
+        let _ = disable_raw_mode();
+        let _ = execute!(io::stdout(), LeaveAlternateScreen);
+    }
+}
+
+impl DisplayBackend for TuiBackend {
+    fn draw(&mut self, frame: &BmpFrame) -> Result<()> {
+        let bmp = Bmp::<BinaryColor>::from_slice(frame.0).map_err(|e| anyhow!("{:?}", e))?;
+        let size = bmp.size();
+
+        let mut pixels = vec![false; (size.width * size.height) as usize];
+        for Pixel(point, color) in bmp.pixels() {
+            let idx = (point.y as u32 * size.width + point.x as u32) as usize;
+            pixels[idx] = color == BinaryColor::On;
+        }
+
+        let status_line = self.status_line.clone();
+        self.terminal.draw(|f| {
+            let area = f.area();
+            let rows = area.height.saturating_sub(1);
+            let cols = area.width;
+
+            let mut lines = Vec::with_capacity(rows as usize + 1);
+            for row in 0..rows {
+                let mut spans = Vec::with_capacity(cols as usize);
+                for col in 0..cols {
+                    let top = sample(&pixels, size, col, cols, row * 2, rows * 2);
+                    let bottom = sample(&pixels, size, col, cols, row * 2 + 1, rows * 2);
+                    spans.push(Span::styled(
+                        "\u{2580}",
+                        Style::default().fg(on_off_color(top)).bg(on_off_color(bottom)),
+                    ));
+                }
+                lines.push(Line::from(spans));
+            }
+            lines.push(Line::from(Span::raw(status_line.clone())));
+
+            f.render_widget(Paragraph::new(lines), area);
+        })?;
+
+        Ok(())
+    }
+
+    fn set_status_line(&mut self, text: &str) {
+        self.status_line = text.to_string();
+    }
+
+    fn poll_events(&mut self) -> Vec<UiEvent> {
+        let mut events = Vec::new();
+        while event::poll(Duration::from_millis(0)).unwrap_or(false) {
+            if let Ok(Event::Key(key)) = event::read() {
+                match key.code {
+                    KeyCode::Char('q') => events.push(UiEvent::Quit),
+                    KeyCode::Char('r') => events.push(UiEvent::ManualRefresh),
+                    KeyCode::Char('s') => events.push(UiEvent::ButtonPress(ButtonPress::Short)),
+                    KeyCode::Char('l') => events.push(UiEvent::ButtonPress(ButtonPress::Long)),
+                    KeyCode::Char('d') => events.push(UiEvent::ButtonPress(ButtonPress::Double)),
+                    _ => debug!("Unhandled key: {:?}", key.code),
+                }
+            }
+        }
+        events
+    }
+}
+
+/// Maps a terminal cell's row/col down to a pixel in the source bitmap.
+fn sample(pixels: &[bool], size: Size, col: u16, cols: u16, row: u16, rows: u16) -> bool {
+    let x = ((col as u32) * size.width / cols.max(1) as u32).min(size.width.saturating_sub(1));
+    let y = ((row as u32) * size.height / rows.max(1) as u32).min(size.height.saturating_sub(1));
+    pixels[(y * size.width + x) as usize]
+}
+
+fn on_off_color(on: bool) -> Color {
+    if on { Color::Black } else { Color::White }
+}
@@ -0,0 +1,150 @@
+use std::{sync::Arc, time::Instant};
+
+use axum::{
+    Json, Router,
+    extract::State,
+    http::StatusCode,
+    response::IntoResponse,
+    routing::{get, post},
+};
+use image::ImageFormat;
+use log::{error, info};
+use serde::Serialize;
+use tokio::{
+    net::TcpListener,
+    sync::{Mutex, mpsc},
+};
+
+use crate::models::DisplayResponse;
+
+/// A trimmed-down view of the last `DisplayResponse` we saw, for `GET /status`.
+#[derive(Serialize, Clone)]
+pub struct StatusSnapshot {
+    pub status: u16,
+    pub filename: Option<String>,
+    pub seconds_until_refresh: Option<u64>,
+    pub reset_firmware: bool,
+    pub update_firmware: Option<bool>,
+    pub firmware_url: Option<String>,
+    /// The special function the server asked the device to perform on its
+    /// next button press.
+    pub special_function: Option<String>,
+    /// Set if the most recent poll attempt failed, cleared on the next
+    /// successful one.
+    pub poll_error: Option<String>,
+}
+
+impl StatusSnapshot {
+    /// `seconds_until_refresh` is filled in later from `ApiState`'s refresh
+    /// schedule instead of here, since it keeps ticking down after this runs.
+    pub fn from_response(resp: &DisplayResponse) -> Self {
+        Self {
+            status: resp.status,
+            filename: resp.filename.clone(),
+            seconds_until_refresh: None,
+            reset_firmware: resp.reset_firmware,
+            update_firmware: resp.update_firmware,
+            firmware_url: resp.firmware_url.clone(),
+            special_function: resp
+                .special_function
+                .as_ref()
+                .map(|f| f.as_str().to_string()),
+            poll_error: None,
+        }
+    }
+}
+
+/// State shared between `web_calls` and the status API.
+#[derive(Default)]
+pub struct ApiState {
+    status: Mutex<Option<StatusSnapshot>>,
+    last_bmp: Mutex<Option<Vec<u8>>>,
+    refresh_schedule: Mutex<Option<(Instant, u64)>>,
+    last_error: Mutex<Option<String>>,
+}
+
+impl ApiState {
+    pub async fn set_status(&self, snapshot: StatusSnapshot) {
+        *self.status.lock().await = Some(snapshot);
+    }
+
+    pub async fn set_last_bmp(&self, bmp_bytes: Vec<u8>) {
+        *self.last_bmp.lock().await = Some(bmp_bytes);
+    }
+
+    pub async fn set_refresh_schedule(&self, total_seconds: u64) {
+        *self.refresh_schedule.lock().await = Some((Instant::now(), total_seconds));
+    }
+
+    /// Marks the current poll as failed.
+    pub async fn set_error(&self, message: impl Into<String>) {
+        *self.last_error.lock().await = Some(message.into());
+    }
+
+    pub async fn clear_error(&self) {
+        *self.last_error.lock().await = None;
+    }
+
+    async fn seconds_until_refresh(&self) -> Option<u64> {
+        let (started_at, total_seconds) = (*self.refresh_schedule.lock().await)?;
+        Some(total_seconds.saturating_sub(started_at.elapsed().as_secs()))
+    }
+}
+
+type AppState = (Arc<ApiState>, mpsc::Sender<()>);
+
+/// Runs the optional local status/control server until the process exits.
+/// Only spawned when `--serve` is passed.
+pub async fn run(addr: String, state: Arc<ApiState>, refresh: mpsc::Sender<()>) -> anyhow::Result<()> {
+    let app = Router::new()
+        .route("/status", get(get_status))
+        .route("/current.png", get(get_current_png))
+        .route("/refresh", post(post_refresh))
+        .with_state((state, refresh));
+
+    info!("Serving status API on http://{addr}");
+    let listener = TcpListener::bind(&addr).await?;
+    axum::serve(listener, app).await?;
+    Ok(())
+}
+
+async fn get_status(State((state, _)): State<AppState>) -> impl IntoResponse {
+    match state.status.lock().await.clone() {
+        Some(mut snapshot) => {
+            snapshot.seconds_until_refresh = state.seconds_until_refresh().await;
+            snapshot.poll_error = state.last_error.lock().await.clone();
+            Json(snapshot).into_response()
+        }
+        None => StatusCode::SERVICE_UNAVAILABLE.into_response(),
+    }
+}
+
+async fn get_current_png(State((state, _)): State<AppState>) -> impl IntoResponse {
+    let Some(bmp_bytes) = state.last_bmp.lock().await.clone() else {
+        return StatusCode::SERVICE_UNAVAILABLE.into_response();
+    };
+
+    let image = match image::load_from_memory_with_format(&bmp_bytes, ImageFormat::Bmp) {
+        Ok(image) => image,
+        Err(err) => {
+            error!("Failed to decode cached BMP for /current.png: {err}");
+            return StatusCode::INTERNAL_SERVER_ERROR.into_response();
+        }
+    };
+
+    let mut png_bytes = Vec::new();
+    if let Err(err) = image.write_to(&mut std::io::Cursor::new(&mut png_bytes), ImageFormat::Png) {
+        error!("Failed to encode PNG for /current.png: {err}");
+        return StatusCode::INTERNAL_SERVER_ERROR.into_response();
+    }
+
+    ([("content-type", "image/png")], png_bytes).into_response()
+}
+
+async fn post_refresh(State((_, refresh)): State<AppState>) -> impl IntoResponse {
+    if refresh.send(()).await.is_err() {
+        error!("Failed to trigger refresh: polling task is gone");
+        return StatusCode::INTERNAL_SERVER_ERROR;
+    }
+    StatusCode::ACCEPTED
+}
@@ -0,0 +1,221 @@
+use std::{collections::HashMap, path::PathBuf, sync::Arc};
+
+use futures::future::{BoxFuture, FutureExt, Shared};
+use log::{debug, warn};
+use reqwest::{Client, StatusCode, header};
+use tokio::sync::Mutex;
+
+/// What cached bytes we had for `image_url` the last time we fetched it.
+#[derive(Clone)]
+struct CachedEntry {
+    bytes: Vec<u8>,
+    etag: Option<String>,
+    last_modified: Option<String>,
+}
+
+/// The result of a fetch: either the server said `304 Not Modified` and we
+/// reused what we had, or the bytes actually changed.
+#[derive(Clone)]
+pub enum FetchOutcome {
+    Unchanged(Vec<u8>),
+    Changed(Vec<u8>),
+}
+
+impl FetchOutcome {
+    pub fn changed(&self) -> bool {
+        matches!(self, FetchOutcome::Changed(_))
+    }
+
+    pub fn into_bytes(self) -> Vec<u8> {
+        match self {
+            FetchOutcome::Unchanged(bytes) | FetchOutcome::Changed(bytes) => bytes,
+        }
+    }
+}
+
+type SharedFetch = Shared<BoxFuture<'static, Result<FetchOutcome, String>>>;
+
+/// Caches downloaded BMP frames by `image_url` and single-flights concurrent
+/// fetches for the same URL.
+#[derive(Clone)]
+pub struct ImageCache {
+    dir: Option<PathBuf>,
+    memory: Arc<Mutex<HashMap<String, CachedEntry>>>,
+    inflight: Arc<Mutex<HashMap<String, SharedFetch>>>,
+}
+
+impl ImageCache {
+    pub fn new(dir: Option<PathBuf>) -> Self {
+        Self {
+            dir,
+            memory: Arc::new(Mutex::new(HashMap::new())),
+            inflight: Arc::new(Mutex::new(HashMap::new())),
+        }
+    }
+
+    /// Reads whatever image we last persisted, regardless of its URL.
+    pub async fn preload_latest(&self) -> Option<Vec<u8>> {
+        let path = self.dir.as_ref()?.join("latest.bmp");
+        tokio::fs::read(&path).await.ok()
+    }
+
+    /// Fetches `image_url`, reusing cached bytes on a conditional-GET `304`.
+    pub async fn fetch(&self, client: &Client, image_url: &str) -> anyhow::Result<FetchOutcome> {
+        // The check-and-insert has to happen under one lock guard, or two
+        // callers racing for the same URL can both see no in-flight future
+        // and each kick off their own fetch.
+        let fut = {
+            let mut inflight = self.inflight.lock().await;
+            match inflight.get(image_url) {
+                Some(existing) => {
+                    debug!("Awaiting in-flight fetch for {image_url}");
+                    existing.clone()
+                }
+                None => {
+                    let client = client.clone();
+                    let image_url_owned = image_url.to_string();
+                    let cache = self.clone();
+                    let fut: SharedFetch = async move {
+                        cache
+                            .fetch_uncached(&client, &image_url_owned)
+                            .await
+                            .map_err(|err| err.to_string())
+                    }
+                    .boxed()
+                    .shared();
+                    inflight.insert(image_url.to_string(), fut.clone());
+                    fut
+                }
+            }
+        };
+
+        let result = fut.await.map_err(|err| anyhow::anyhow!(err));
+        self.inflight.lock().await.remove(image_url);
+        result
+    }
+
+    async fn fetch_uncached(&self, client: &Client, image_url: &str) -> anyhow::Result<FetchOutcome> {
+        let cached = self.memory.lock().await.get(image_url).cloned();
+
+        let mut request = client.get(image_url);
+        if let Some(cached) = &cached {
+            if let Some(etag) = &cached.etag {
+                request = request.header(header::IF_NONE_MATCH, etag);
+            }
+            if let Some(last_modified) = &cached.last_modified {
+                request = request.header(header::IF_MODIFIED_SINCE, last_modified);
+            }
+        }
+
+        let response = request.send().await?;
+
+        if response.status() == StatusCode::NOT_MODIFIED {
+            let cached = cached.ok_or_else(|| {
+                anyhow::anyhow!("Server returned 304 but we have no cached copy of {image_url}")
+            })?;
+            debug!("Image unchanged (304): {image_url}");
+            return Ok(FetchOutcome::Unchanged(cached.bytes));
+        }
+
+        let etag = header_str(&response, header::ETAG);
+        let last_modified = header_str(&response, header::LAST_MODIFIED);
+        let bytes = response.bytes().await?.to_vec();
+
+        self.persist(&bytes).await;
+        self.memory.lock().await.insert(
+            image_url.to_string(),
+            CachedEntry {
+                bytes: bytes.clone(),
+                etag,
+                last_modified,
+            },
+        );
+
+        Ok(FetchOutcome::Changed(bytes))
+    }
+
+    async fn persist(&self, bytes: &[u8]) {
+        let Some(dir) = &self.dir else {
+            return;
+        };
+        if let Err(err) = tokio::fs::create_dir_all(dir).await {
+            warn!("Failed to create image cache dir {}: {err}", dir.display());
+            return;
+        }
+        if let Err(err) = tokio::fs::write(dir.join("latest.bmp"), bytes).await {
+            warn!("Failed to persist cached image to {}: {err}", dir.display());
+        }
+    }
+}
+
+fn header_str(response: &reqwest::Response, name: header::HeaderName) -> Option<String> {
+    response
+        .headers()
+        .get(name)
+        .and_then(|value| value.to_str().ok())
+        .map(str::to_string)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    use axum::{Router, http::StatusCode, routing::get};
+    use tokio::net::TcpListener;
+
+    use super::*;
+
+    // Spins up a tiny local server so fetch()/fetch_uncached() exercise real
+    // reqwest calls instead of needing a mock http client.
+    async fn spawn_server(router: Router) -> String {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        tokio::spawn(async move {
+            axum::serve(listener, router).await.unwrap();
+        });
+        format!("http://{addr}")
+    }
+
+    #[tokio::test]
+    async fn fetch_single_flights_concurrent_callers() {
+        let hits = Arc::new(AtomicUsize::new(0));
+        let hits_for_handler = hits.clone();
+        let router = Router::new().route(
+            "/image.bmp",
+            get(move || {
+                let hits = hits_for_handler.clone();
+                async move {
+                    hits.fetch_add(1, Ordering::SeqCst);
+                    tokio::time::sleep(std::time::Duration::from_millis(50)).await;
+                    b"bmp-bytes".to_vec()
+                }
+            }),
+        );
+        let base_url = spawn_server(router).await;
+        let image_url = format!("{base_url}/image.bmp");
+
+        let cache = ImageCache::new(None);
+        let client = Client::new();
+        let (first, second) = tokio::join!(
+            cache.fetch(&client, &image_url),
+            cache.fetch(&client, &image_url)
+        );
+
+        assert!(first.unwrap().changed());
+        assert!(second.unwrap().changed());
+        assert_eq!(hits.load(Ordering::SeqCst), 1);
+    }
+
+    #[tokio::test]
+    async fn fetch_uncached_304_without_prior_cache_errors() {
+        let router = Router::new().route("/image.bmp", get(|| async { StatusCode::NOT_MODIFIED }));
+        let base_url = spawn_server(router).await;
+        let image_url = format!("{base_url}/image.bmp");
+
+        let cache = ImageCache::new(None);
+        let client = Client::new();
+        let result = cache.fetch_uncached(&client, &image_url).await;
+
+        assert!(result.is_err());
+    }
+}
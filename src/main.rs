@@ -1,24 +1,35 @@
-use anyhow::{Result, anyhow};
+use anyhow::{Context, Result, anyhow};
 use clap::Parser;
 use dotenv::dotenv;
-use embedded_graphics::{image::Image, pixelcolor::BinaryColor, prelude::*};
-use embedded_graphics_simulator::{
-    BinaryColorTheme, OutputSettingsBuilder, SimulatorDisplay, SimulatorEvent, Window,
-    sdl2::Keycode,
-};
+use embedded_graphics_simulator::BinaryColorTheme;
 use env_logger::Env;
+use http_api::ApiState;
 use log::{debug, error, info};
 use models::DisplayResponse;
 use reqwest::{Client, header};
-use std::time::Duration;
-use tinybmp::Bmp;
+use std::{
+    path::PathBuf,
+    sync::Arc,
+    time::{Duration, Instant},
+};
 use tokio::{
     signal,
     sync::mpsc::{self, Sender},
     time::sleep,
 };
 
+use config::Config;
+use display_backend::{BmpFrame, ButtonPress, DisplayBackend, SimulatorBackend, TuiBackend, UiEvent};
+use embedded_graphics::prelude::Size;
+use image_cache::ImageCache;
+use notify::NotifySettings;
+
+mod config;
+mod display_backend;
+mod http_api;
+mod image_cache;
 mod models;
+mod notify;
 
 static APP_USER_AGENT: &str = concat!(env!("CARGO_PKG_NAME"), "/", env!("CARGO_PKG_VERSION"),);
 
@@ -26,17 +37,120 @@ static APP_USER_AGENT: &str = concat!(env!("CARGO_PKG_NAME"), "/", env!("CARGO_P
 #[derive(Parser, Debug)]
 #[command(version, about)]
 struct ApiArguments {
-    /// Your API key found in TRMNL's developer settings
+    /// Your API key found in TRMNL's developer settings. Overrides the
+    /// API_KEY env var and the config file's profile.
     #[arg(short, long)]
-    api_key: String,
+    api_key: Option<String>,
+
+    /// Base url for the API. Overrides the API_URL_BASE env var and the
+    /// config file's profile.
+    #[arg(short, long)]
+    base_url: Option<String>,
+
+    /// Named device profile to load from the config file.
+    #[arg(short, long)]
+    profile: Option<String>,
+
+    /// Bind address for a local status/control API, e.g. 127.0.0.1:8080.
+    /// Exposes GET /status, GET /current.png, and POST /refresh.
+    #[arg(long)]
+    serve: Option<String>,
 
-    /// Base url for the API
-    #[arg(short, long, default_value = "https://usetrmnl.com")]
+    /// Render in the terminal with ratatui instead of opening an SDL window.
+    /// Useful on servers or over SSH with no display attached.
+    #[arg(long)]
+    tui: bool,
+
+    /// Directory to persist the most recently downloaded image to, for
+    /// offline replay on startup. Unchanged images are skipped via
+    /// conditional GETs within a run whether or not this is set.
+    #[arg(long)]
+    cache_dir: Option<PathBuf>,
+
+    /// Path to a sound clip to play whenever the display actually updates
+    /// or the API responds with an error status. Overrides the config
+    /// file's profile.
+    #[arg(long)]
+    notify_sound: Option<PathBuf>,
+
+    /// Show a desktop notification on the same events as --notify-sound.
+    #[arg(long)]
+    notify_desktop: bool,
+}
+
+/// Device settings fully resolved from CLI args, env vars, and the config
+/// file's profile, in that priority order.
+struct ResolvedArgs {
+    api_key: String,
     base_url: String,
+    serve: Option<String>,
+    tui: bool,
+    cache_dir: Option<PathBuf>,
+    theme: BinaryColorTheme,
+    size: Size,
+    notify: NotifySettings,
+}
+
+fn resolve_args(cli: ApiArguments) -> Result<ResolvedArgs> {
+    let config = Config::load()?;
+    // A profile requested by name must exist; only fall back silently when
+    // the user didn't ask for one at all.
+    let profile = match cli.profile.as_deref() {
+        Some(name) => Some(
+            config
+                .as_ref()
+                .with_context(|| format!("--profile {name} was given, but no config file was found"))?
+                .profile(Some(name))?,
+        ),
+        None => config.as_ref().and_then(|config| config.profile(None).ok()),
+    };
+
+    let api_key = cli
+        .api_key
+        .clone()
+        .or_else(|| std::env::var("API_KEY").ok())
+        .or_else(|| profile.map(|p| p.api_key.clone()))
+        .context("No API key given via --api-key, API_KEY, or a config profile")?;
+
+    let base_url = cli
+        .base_url
+        .clone()
+        .or_else(|| std::env::var("API_URL_BASE").ok())
+        .or_else(|| profile.map(|p| p.base_url.clone()))
+        .unwrap_or_else(|| "https://usetrmnl.com".to_string());
+
+    let (theme, size) = match profile {
+        Some(profile) => (
+            profile.theme.to_simulator_theme(),
+            Size::new(profile.width, profile.height),
+        ),
+        None => (BinaryColorTheme::Default, Size::new(800, 480)),
+    };
+
+    let notify = NotifySettings {
+        sound_path: cli
+            .notify_sound
+            .or_else(|| profile.and_then(|p| p.notify_sound.clone())),
+        desktop: cli.notify_desktop || profile.is_some_and(|p| p.notify_desktop),
+    };
+
+    Ok(ResolvedArgs {
+        api_key,
+        base_url,
+        serve: cli.serve,
+        tui: cli.tui,
+        cache_dir: cli.cache_dir,
+        theme,
+        size,
+        notify,
+    })
 }
 
 enum Message {
     NewImage(Vec<u8>),
+    /// Tells `run_display` how long until the next scheduled poll, so it can
+    /// show a countdown (currently only the TUI backend renders it).
+    RefreshScheduled { seconds: u64 },
 }
 
 #[tokio::main]
@@ -45,27 +159,51 @@ async fn main() -> Result<()> {
     let env = Env::default().filter_or("RUST_LOG", "info");
     env_logger::init_from_env(env);
 
-    let env_api_key = std::env::var("API_KEY");
-    let env_base_url = std::env::var("API_URL_BASE");
-
-    let args = if env_api_key.is_ok() && env_base_url.is_ok() {
-        info!("Using API_KEY and API_URL_BASE from environment variables");
-        ApiArguments {
-            api_key: env_api_key.unwrap(),
-            base_url: env_base_url.unwrap(),
-        }
-    } else {
-        info!("Using command-line arguments for API_KEY and API_URL_BASE");
-        ApiArguments::parse()
-    };
+    let args = resolve_args(ApiArguments::parse())?;
+    info!(
+        "Using base_url={}, tui={}, serve={:?}",
+        args.base_url, args.tui, args.serve
+    );
 
     // I think 1 will be fine for now, but I might need to increase this later
     let (tx, rx) = mpsc::channel::<Message>(5);
     // Used to early bail a tokio::sleep in web_calls
     let (early_timeout_bail_sender, early_timeout_bail_receiver) = mpsc::channel::<()>(1);
+    // Carries a simulated button press from run_display to the next /api/display poll
+    let (button_tx, button_rx) = mpsc::channel::<ButtonPress>(5);
+
+    let tui = args.tui;
+    let theme = args.theme;
+    let size = args.size;
+    let api_state = Arc::new(ApiState::default());
+
+    if let Some(addr) = args.serve.clone() {
+        let api_state = api_state.clone();
+        let refresh = early_timeout_bail_sender.clone();
+        tokio::spawn(async move {
+            if let Err(err) = http_api::run(addr, api_state, refresh).await {
+                error!("Status API server failed: {err}");
+            }
+        });
+    }
+
+    let image_cache = ImageCache::new(args.cache_dir.clone());
+    if let Some(bytes) = image_cache.preload_latest().await {
+        info!("Replaying last cached image while waiting on the first poll");
+        api_state.set_last_bmp(bytes.clone()).await;
+        let _ = tx.send(Message::NewImage(bytes)).await;
+    }
 
     tokio::spawn(async move {
-        let _ = web_calls(tx, early_timeout_bail_receiver, args).await;
+        let _ = web_calls(
+            tx,
+            early_timeout_bail_receiver,
+            args,
+            api_state,
+            image_cache,
+            button_rx,
+        )
+        .await;
     });
 
     tokio::select! {
@@ -73,7 +211,7 @@ async fn main() -> Result<()> {
             info!("Ctrl-C received, shutting down");
             return Ok(());
         },
-        _ = run_display(rx, early_timeout_bail_sender) => {},
+        _ = run_display(rx, early_timeout_bail_sender, button_tx, tui, theme, size) => {},
     }
 
     Ok(())
@@ -82,51 +220,65 @@ async fn main() -> Result<()> {
 async fn run_display(
     mut rx: mpsc::Receiver<Message>,
     early_timeout_bail: mpsc::Sender<()>,
+    button_press: mpsc::Sender<ButtonPress>,
+    tui: bool,
+    theme: BinaryColorTheme,
+    size: Size,
 ) -> Result<()> {
-    let output_settings = OutputSettingsBuilder::new()
-        .scale(1)
-        .pixel_spacing(1)
-        .theme(BinaryColorTheme::Default)
-        .build();
-    let mut window = Window::new("TRMNL", &output_settings);
-    //800x480
-    let mut display = SimulatorDisplay::<BinaryColor>::new(Size::new(800, 480));
+    let mut backend: Box<dyn DisplayBackend> = if tui {
+        Box::new(TuiBackend::new()?)
+    } else {
+        Box::new(SimulatorBackend::new(theme, size))
+    };
+
+    let mut countdown: Option<(Instant, u64)> = None;
+    let mut last_frame: Option<Vec<u8>> = None;
+    let mut last_remaining: Option<u64> = None;
 
     loop {
-        _ = match rx.try_recv() {
-            Ok(message) => match message {
-                Message::NewImage(bmp_bytes) => {
-                    info!("New display update received");
-                    let bmp = Bmp::<BinaryColor>::from_slice(&bmp_bytes).unwrap();
-                    let _ = Image::new(&bmp, Point::zero()).draw(&mut display);
+        match rx.try_recv() {
+            Ok(Message::NewImage(bmp_bytes)) => {
+                info!("New display update received");
+                if let Err(err) = backend.draw(&BmpFrame(&bmp_bytes)) {
+                    error!("Failed to draw frame: {err}");
                 }
-            },
+                last_frame = Some(bmp_bytes);
+            }
+            Ok(Message::RefreshScheduled { seconds }) => {
+                countdown = Some((Instant::now(), seconds));
+            }
             Err(_) => {}
-        };
+        }
 
-        window.update(&display);
+        if let Some((started_at, total_seconds)) = countdown {
+            let remaining = total_seconds.saturating_sub(started_at.elapsed().as_secs());
+            backend.set_status_line(&format!(
+                "next refresh in {remaining}s (r: refresh now, q: quit)"
+            ));
+            // The TUI only renders the status line as part of a frame, so
+            // redraw the last frame whenever the countdown changes.
+            if tui && last_remaining != Some(remaining) {
+                last_remaining = Some(remaining);
+                if let Some(frame) = &last_frame {
+                    if let Err(err) = backend.draw(&BmpFrame(frame)) {
+                        error!("Failed to redraw frame: {err}");
+                    }
+                }
+            }
+        }
 
-        for event in window.events() {
+        for event in backend.poll_events() {
             match event {
-                SimulatorEvent::Quit => {
-                    return Ok(());
+                UiEvent::Quit => return Ok(()),
+                UiEvent::ManualRefresh => {
+                    let _ = early_timeout_bail.send(()).await;
+                }
+                UiEvent::ButtonPress(press) => {
+                    let _ = button_press.send(press).await;
                 }
-                SimulatorEvent::KeyDown {
-                    keycode,
-                    keymod: _,
-                    repeat: _,
-                } => match keycode {
-                    Keycode::Return => {
-                        debug!("Return key pressed");
-                        let _ = early_timeout_bail.send(()).await;
-                    }
-                    _ => {
-                        debug!("Unhandled keycode: {:?}", keycode);
-                    }
-                },
-                _ => {}
             }
         }
+
         // Have to always update the display or it crashes. Faster fps (lower sleep) helps keep the process down
         // Get to high enough and it will crash
         // And if it's too high keypresses are missed
@@ -137,7 +289,10 @@ async fn run_display(
 async fn web_calls(
     sender: Sender<Message>,
     mut early_timeout_bail: mpsc::Receiver<()>,
-    config: ApiArguments,
+    config: ResolvedArgs,
+    api_state: Arc<ApiState>,
+    image_cache: ImageCache,
+    mut button_press: mpsc::Receiver<ButtonPress>,
 ) -> Result<()> {
     let mut headers = header::HeaderMap::new();
     headers.insert(
@@ -165,13 +320,23 @@ async fn web_calls(
             first_run = false;
         }
 
-        let result = client
-            .get(format!("{}/api/display", config.base_url))
-            .send()
-            .await;
+        let mut pending_press = None;
+        while let Ok(press) = button_press.try_recv() {
+            pending_press = Some(press);
+        }
+
+        let mut request = client.get(format!("{}/api/display", config.base_url));
+        if let Some(press) = pending_press {
+            debug!("Forwarding simulated {}-press to next poll", press.as_header_value());
+            request = request.header("button-press", press.as_header_value());
+        }
+
+        let result = request.send().await;
 
         if result.is_err() {
             error!("Failed to get response from api");
+            api_state.set_error("Failed to get response from api").await;
+            api_state.set_refresh_schedule(sleep_time).await;
             continue;
         }
         let result = result.unwrap();
@@ -181,35 +346,63 @@ async fn web_calls(
         if parse_result.is_err() {
             error!("Failed to parse response from api\nStatus: {}", status);
             error!("{:#?}", body_as_string);
+            api_state
+                .set_error(format!("Failed to parse response from api (status {status})"))
+                .await;
+            api_state.set_refresh_schedule(sleep_time).await;
             continue;
         }
 
         info!("{parse_result:#?}");
 
         let resp = parse_result?;
+        match &resp.special_function {
+            Some(models::SpecialFunction::None) | None => {}
+            Some(special_function) => {
+                info!(
+                    "Server requested special function on next button press: {}",
+                    special_function.as_str()
+                );
+            }
+        }
+        api_state
+            .set_status(http_api::StatusSnapshot::from_response(&resp))
+            .await;
         //Not sure on a successful one yet. I think its 0
         if resp.status == 500 {
-            match resp.error {
-                Some(err_msg) => {
-                    error!("Error from api: {}", err_msg);
-                }
-                None => {
-                    error!("Web request failed but no error from api.")
-                }
-            };
+            let err_msg = resp
+                .error
+                .unwrap_or_else(|| "Web request failed but no error from api.".to_string());
+            error!("Error from api: {}", err_msg);
+            config.notify.notify_error(&err_msg);
 
+            api_state.set_error(err_msg).await;
+            api_state.set_refresh_schedule(sleep_time).await;
             continue;
         }
 
         match resp.image_url {
-            Some(image_url) => {
-                let new_bytes = client.get(image_url).send().await?.bytes().await?.to_vec();
-                let sender = sender.send(Message::NewImage(new_bytes)).await;
-                if sender.is_err() {
-                    error!("Failed to send new image to display");
-                    return Err(anyhow!("Failed to send new image to display"));
+            Some(image_url) => match image_cache.fetch(&client, &image_url).await {
+                Ok(outcome) if outcome.changed() => {
+                    let new_bytes = outcome.into_bytes();
+                    api_state.set_last_bmp(new_bytes.clone()).await;
+                    let sender = sender.send(Message::NewImage(new_bytes)).await;
+                    if sender.is_err() {
+                        error!("Failed to send new image to display");
+                        return Err(anyhow!("Failed to send new image to display"));
+                    }
+                    config.notify.notify_new_image(resp.filename.as_deref());
                 }
-            }
+                Ok(_unchanged) => {
+                    debug!("Image unchanged since last poll, skipping redraw");
+                }
+                Err(err) => {
+                    error!("Failed to fetch image: {err}");
+                    api_state.set_error(format!("Failed to fetch image: {err}")).await;
+                    api_state.set_refresh_schedule(sleep_time).await;
+                    continue;
+                }
+            },
             None => {
                 return Err(anyhow!(
                     "An image_url was not returned from the api response"
@@ -217,6 +410,8 @@ async fn web_calls(
             }
         }
 
+        api_state.clear_error().await;
+
         match resp.refresh_rate {
             Some(refresh_rate) => {
                 sleep_time = refresh_rate;
@@ -226,5 +421,11 @@ async fn web_calls(
                 info!("No refresh rate from api, sleeping for 10mins")
             }
         }
+        api_state.set_refresh_schedule(sleep_time).await;
+        let _ = sender
+            .send(Message::RefreshScheduled {
+                seconds: sleep_time,
+            })
+            .await;
     }
 }
@@ -1,4 +1,4 @@
-use serde::{Deserialize, Serialize};
+use serde::{Deserialize, Serialize, Serializer, de::Deserializer};
 
 /// It looks like all responses return 200 ok and then use the status field.
 #[derive(Serialize, Deserialize, Debug)]
@@ -17,6 +17,70 @@ pub struct DisplayResponse {
     pub reset_firmware: bool,
     pub update_firmware: Option<bool>,
     pub firmware_url: Option<String>,
-    ///I think this is an enum so will swap over later when I learn more about the api
-    pub special_function: Option<String>,
+    /// The action the next device button press should perform
+    pub special_function: Option<SpecialFunction>,
+}
+
+/// The action a device button press should perform, as sent by the API in
+/// `special_function`. Falls back to `Unknown` for values we don't know
+/// about yet instead of failing to deserialize the whole response.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum SpecialFunction {
+    Identify,
+    Sleep,
+    AddWifi,
+    Restart,
+    None,
+    Unknown(String),
+}
+
+impl Serialize for SpecialFunction {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+    {
+        serializer.serialize_str(self.as_str())
+    }
+}
+
+impl<'de> Deserialize<'de> for SpecialFunction {
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        let raw = String::deserialize(deserializer)?;
+        Ok(match raw.as_str() {
+            "identify" => SpecialFunction::Identify,
+            "sleep" => SpecialFunction::Sleep,
+            "add_wifi" => SpecialFunction::AddWifi,
+            "restart" => SpecialFunction::Restart,
+            "none" => SpecialFunction::None,
+            _ => SpecialFunction::Unknown(raw),
+        })
+    }
+}
+
+impl SpecialFunction {
+    pub fn as_str(&self) -> &str {
+        match self {
+            SpecialFunction::Identify => "identify",
+            SpecialFunction::Sleep => "sleep",
+            SpecialFunction::AddWifi => "add_wifi",
+            SpecialFunction::Restart => "restart",
+            SpecialFunction::None => "none",
+            SpecialFunction::Unknown(raw) => raw,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn unrecognized_special_function_round_trips_as_unknown() {
+        let parsed: SpecialFunction = serde_json::from_str("\"future_function\"").unwrap();
+        assert_eq!(parsed, SpecialFunction::Unknown("future_function".to_string()));
+        assert_eq!(serde_json::to_string(&parsed).unwrap(), "\"future_function\"");
+    }
 }
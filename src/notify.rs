@@ -0,0 +1,62 @@
+use std::path::PathBuf;
+
+use log::{error, warn};
+
+/// Optional audio/desktop hooks, configured via `--notify-sound` / `--notify-desktop`.
+#[derive(Clone, Default)]
+pub struct NotifySettings {
+    pub sound_path: Option<PathBuf>,
+    pub desktop: bool,
+}
+
+impl NotifySettings {
+    /// Fired once a new image has been sent to the display.
+    pub fn notify_new_image(&self, filename: Option<&str>) {
+        let body = match filename {
+            Some(name) => format!("New screen: {name}"),
+            None => "New screen received".to_string(),
+        };
+        self.fire("BYODT", &body);
+    }
+
+    /// Fired when the API responds with an error status.
+    pub fn notify_error(&self, error: &str) {
+        self.fire("BYODT - polling error", error);
+    }
+
+    fn fire(&self, summary: &str, body: &str) {
+        if let Some(path) = self.sound_path.clone() {
+            // Play on a blocking task so a slow/odd audio backend never
+            // stalls the poll or render loop.
+            tokio::task::spawn_blocking(move || {
+                if let Err(err) = play_sound(&path) {
+                    warn!("Failed to play notification sound: {err}");
+                }
+            });
+        }
+
+        if self.desktop {
+            let summary = summary.to_string();
+            let body = body.to_string();
+            tokio::task::spawn_blocking(move || {
+                if let Err(err) = notify_rust::Notification::new()
+                    .summary(&summary)
+                    .body(&body)
+                    .show()
+                {
+                    error!("Failed to show desktop notification: {err}");
+                }
+            });
+        }
+    }
+}
+
+fn play_sound(path: &std::path::Path) -> anyhow::Result<()> {
+    let (_stream, stream_handle) = rodio::OutputStream::try_default()?;
+    let file = std::fs::File::open(path)?;
+    let source = rodio::Decoder::new(std::io::BufReader::new(file))?;
+    let sink = rodio::Sink::try_new(&stream_handle)?;
+    sink.append(source);
+    sink.sleep_until_end();
+    Ok(())
+}